@@ -0,0 +1,38 @@
+extern crate secp256k1;
+extern crate rand;
+
+use self::secp256k1::{Secp256k1, Message, Signature};
+use self::secp256k1::key::{SecretKey, PublicKey};
+
+pub type PrivateKey = SecretKey;
+
+/// A generated keypair, as returned by `generate_keypair`.
+pub struct KeyPair {
+    pub private_key: PrivateKey,
+    pub public_key: PublicKey,
+}
+
+/// Generates a fresh secp256k1 keypair using the operating system RNG.
+pub fn generate_keypair() -> KeyPair {
+    let secp = Secp256k1::new();
+    let mut rng = rand::os::OsRng::new().expect("failed to access OS RNG");
+    let (private_key, public_key) = secp.generate_keypair(&mut rng);
+    KeyPair { private_key, public_key }
+}
+
+/// Signs a 32-byte digest (the caller is responsible for hashing the
+/// message first, e.g. with SHA-256) with the given private key.
+pub fn sign(digest: &[u8; 32], private_key: &PrivateKey) -> Signature {
+    let secp = Secp256k1::new();
+    let message = Message::from_slice(digest).expect("digest is exactly 32 bytes");
+    secp.sign(&message, private_key)
+}
+
+/// Verifies that `signature` over `digest` was produced by `public_key`.
+pub fn verify(digest: &[u8; 32], signature: &Signature, public_key: &PublicKey) -> bool {
+    let secp = Secp256k1::new();
+    match Message::from_slice(digest) {
+        Ok(message) => secp.verify(&message, signature, public_key).is_ok(),
+        Err(_) => false,
+    }
+}
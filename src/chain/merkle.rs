@@ -0,0 +1,187 @@
+extern crate crypto;
+
+use self::crypto::digest::Digest;
+use self::crypto::sha2::Sha256;
+
+use chain::transaction::{self, Transaction};
+use chain::Hash;
+
+const ZERO_HASH: Hash = [0; 32];
+
+// Domain-separation prefixes for leaf vs. internal node hashes (RFC 6962
+// style), so a leaf hash can never be mistaken for an internal node hash
+// when walking a proof.
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+fn sha256(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.input(data);
+
+    let mut result = [0; 32];
+    hasher.result(&mut result);
+    result
+}
+
+/// Hashes a single transaction the same way `merkle_root`/`merkle_proof` do,
+/// so callers can recompute the leaf a proof from those functions should be
+/// checked against.
+pub fn leaf_hash(tx: &Transaction) -> Hash {
+    let mut bytes = vec![LEAF_PREFIX];
+    bytes.extend(transaction::as_bytes(tx));
+    sha256(&bytes)
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = Vec::with_capacity(1 + 64);
+    bytes.push(NODE_PREFIX);
+    bytes.extend_from_slice(left);
+    bytes.extend_from_slice(right);
+    sha256(&bytes)
+}
+
+fn leaves(txs: &[Transaction]) -> Vec<Hash> {
+    txs.iter().map(leaf_hash).collect()
+}
+
+/// Combines a level of the tree into the level above it. A trailing node
+/// with no pair (an odd-sized level) is carried up unchanged instead of
+/// being hashed with a duplicate of itself: hashing a node with a copy of
+/// itself is indistinguishable from a level that genuinely contained two
+/// equal, adjacent hashes, which is exactly the CVE-2012-2459 Merkle
+/// malleability (e.g. `[A,B,C]` and `[A,B,C,C]` would otherwise root
+/// identically). Carrying the lone node forward instead means only a
+/// *combined* hash ever appears where a duplication used to, so the two
+/// transaction sets can no longer collide.
+fn combine_level(level: &[Hash]) -> Vec<Hash> {
+    level.chunks(2)
+        .map(|pair| if pair.len() == 2 { node_hash(&pair[0], &pair[1]) } else { pair[0] })
+        .collect()
+}
+
+/// Computes the Merkle root over a block's transactions: leaves are SHA-256
+/// hashes of each transaction's bytes, and adjacent pairs are hashed
+/// together up the tree until a single root remains. An empty transaction
+/// list roots to the all-zero hash.
+pub fn merkle_root(txs: &[Transaction]) -> Hash {
+    if txs.is_empty() {
+        return ZERO_HASH;
+    }
+
+    let mut level = leaves(txs);
+    while level.len() > 1 {
+        level = combine_level(&level);
+    }
+
+    level[0]
+}
+
+/// Builds an inclusion proof for `txs[tx_index]`: a path of sibling hashes
+/// from the leaf to the root, each tagged with whether the sibling sits to
+/// the right (`true`) or left (`false`) of the node on our path. A level
+/// where our node has no sibling (it was carried up unchanged) contributes
+/// no step to the proof.
+pub fn merkle_proof(txs: &[Transaction], tx_index: usize) -> Vec<(Hash, bool)> {
+    assert!(tx_index < txs.len());
+
+    let mut level = leaves(txs);
+    let mut index = tx_index;
+    let mut proof = Vec::new();
+
+    while level.len() > 1 {
+        let is_unpaired = index == level.len() - 1 && !level.len().is_multiple_of(2);
+        if !is_unpaired {
+            let sibling_is_right = index.is_multiple_of(2);
+            let sibling_index = if sibling_is_right { index + 1 } else { index - 1 };
+            proof.push((level[sibling_index], sibling_is_right));
+        }
+
+        level = combine_level(&level);
+        index /= 2;
+    }
+
+    proof
+}
+
+/// Recomputes the root implied by `leaf` and `proof` and checks it matches
+/// `root`, enabling light-client style verification of inclusion without
+/// the full block.
+pub fn verify_merkle_proof(leaf: Hash, proof: &[(Hash, bool)], root: Hash) -> bool {
+    let mut current = leaf;
+    for &(sibling, sibling_is_right) in proof {
+        current = if sibling_is_right {
+            node_hash(&current, &sibling)
+        } else {
+            node_hash(&sibling, &current)
+        };
+    }
+
+    current == root
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merkle_root, merkle_proof, verify_merkle_proof};
+    use chain::keys::generate_keypair;
+    use chain::transaction::sign_transaction;
+
+    fn make_tx(amount: u64) -> super::Transaction {
+        let sender = generate_keypair();
+        let recipient = generate_keypair();
+        sign_transaction(&sender, recipient.public_key, amount)
+    }
+
+    #[test]
+    fn proof_verifies_for_every_leaf_in_odd_sized_tree() {
+        let txs = vec![make_tx(1), make_tx(2), make_tx(3)];
+        let root = merkle_root(&txs);
+
+        for (i, tx) in txs.iter().enumerate() {
+            let proof = merkle_proof(&txs, i);
+            let leaf = super::leaf_hash(tx);
+            assert!(verify_merkle_proof(leaf, &proof, root));
+        }
+    }
+
+    #[test]
+    fn proof_fails_against_wrong_root() {
+        let txs = vec![make_tx(1), make_tx(2)];
+        let other_root = merkle_root(&[make_tx(9)]);
+
+        let proof = merkle_proof(&txs, 0);
+        let leaf = super::leaf_hash(&txs[0]);
+        assert!(!verify_merkle_proof(leaf, &proof, other_root));
+    }
+
+    #[test]
+    fn duplicated_last_transaction_does_not_collide_with_distinct_root() {
+        // CVE-2012-2459: carrying an unpaired last node up unchanged,
+        // instead of hashing it with a duplicate of itself, means a real
+        // trailing duplicate transaction (which *does* get hashed with its
+        // pair) can no longer root identically to the distinct list it
+        // would naively pad to.
+        let a = make_tx(1);
+        let b = make_tx(2);
+        let c = make_tx(3);
+
+        let three = vec![a.clone(), b.clone(), c.clone()];
+        let four = vec![a, b, c.clone(), c];
+
+        assert_ne!(merkle_root(&three), merkle_root(&four));
+    }
+
+    #[test]
+    fn accepts_adjacent_duplicate_transactions() {
+        // Two transactions can be byte-identical on entirely legitimate
+        // input (e.g. paying the same recipient the same amount twice in a
+        // block), since secp256k1 signs deterministically (RFC 6979). That
+        // must not panic.
+        let tx = make_tx(1);
+        let txs = vec![tx.clone(), tx];
+
+        let root = merkle_root(&txs);
+        let proof = merkle_proof(&txs, 1);
+        let leaf = super::leaf_hash(&txs[1]);
+        assert!(verify_merkle_proof(leaf, &proof, root));
+    }
+}
@@ -1,17 +1,45 @@
 extern crate crypto;
 extern crate byteorder;
+extern crate chrono;
+extern crate num_bigint;
+extern crate num_cpus;
+extern crate rand;
+
+pub mod keys;
+pub mod merkle;
+pub mod serialization;
+pub mod target;
+pub mod transaction;
 
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use self::chrono::Utc;
 use self::crypto::sha2::Sha256;
 use self::crypto::digest::Digest;
 use self::byteorder::{BigEndian, WriteBytesExt};
+use self::num_bigint::BigUint;
+use self::target::{CompactTarget, compact_from_target, target_from_compact};
+use self::transaction::Transaction;
 
-pub type Nonce = u32;
+pub type Nonce = u64;
+pub type Salt = u64;
 pub type Hash = [u8; 32];
-pub type Data = Vec<u8>;
+pub type Data = Vec<Transaction>;
 
 const ZERO_HASH: Hash = [0; 32];
-const DIFFICULTY: u32 = 4;
+// Compact target roughly equivalent to the old 4-leading-zero-bit difficulty.
+const INITIAL_BITS: CompactTarget = 0x200fffff;
+
+// Difficulty retargets every `RETARGET_INTERVAL` blocks to bring the actual
+// time taken for that window back towards `RETARGET_INTERVAL *
+// TARGET_BLOCK_SECONDS`, with the adjustment clamped to at most
+// `MAX_RETARGET_FACTOR`x in either direction per window (mirrors Bitcoin's
+// retargeting to avoid wild swings from a handful of lucky or unlucky blocks).
+const RETARGET_INTERVAL: u64 = 10;
+const TARGET_BLOCK_SECONDS: i64 = 60;
+const MAX_RETARGET_FACTOR: i64 = 4;
 
 pub trait Blockchain {
     fn init() -> Self;
@@ -19,6 +47,7 @@ pub trait Blockchain {
     fn verify(&self) -> Result<(), String>;
 }
 
+#[derive(Serialize, Deserialize, PartialEq)]
 pub struct Chain {
     pub blocks: Vec<Block>
 }
@@ -39,25 +68,30 @@ impl Blockchain for Chain {
 
 impl fmt::Debug for Chain {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Chain:\n")?;
+        writeln!(f, "Chain:")?;
         for block in &self.blocks {
             let hash = hash(block);
-            write!(f, "\t[id={}, nonce={}, data={}, prev={}, hash={}]\n", block.id, block.nonce,
-                   bytes_to_str(&block.data), bytes_to_str(&block.prev_hash), bytes_to_str(&hash))?;
+            writeln!(f, "\t[id={}, nonce={}, salt={}, bits={:08x}, timestamp={}, txs={}, merkle_root={}, prev={}, hash={}]", block.id, block.nonce, block.salt, block.bits,
+                   block.timestamp, block.data.len(), bytes_to_str(&block.merkle_root), bytes_to_str(&block.prev_hash), bytes_to_str(&hash))?;
         }
         Ok(())
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Block {
     pub id: u64,
     pub nonce: Nonce,
+    pub salt: Salt,
+    pub bits: CompactTarget,
+    pub timestamp: i64,
+    pub merkle_root: Hash,
     pub data: Data,
     pub prev_hash: Hash,
 }
 
 fn push(chain: &mut Chain, data: Data) {
-    let new_block = make_block(chain.blocks.last(), data);
+    let new_block = make_block(&chain.blocks, data);
     info!("Added block to chain with id={}", new_block.id);
     chain.blocks.push(new_block);
 }
@@ -71,6 +105,8 @@ fn init() -> Chain {
 
 fn verify(chain: &Chain) -> Result<(), String> {
     let mut prev_hash = ZERO_HASH;
+    let mut prev_timestamp: Option<i64> = None;
+
     for (i, block) in chain.blocks.iter().enumerate() {
         if block.id != (i as u64) {
             return Err(format!("Id mismatch at {} - expected {} but found {}", i, i, block.id));
@@ -78,17 +114,72 @@ fn verify(chain: &Chain) -> Result<(), String> {
         if block.prev_hash != prev_hash {
             return Err(format!("Link broken at {} - expected hash {} but calculated {}", i, bytes_to_str(&prev_hash), bytes_to_str(&block.prev_hash)));
         }
+        if let Some(prev_ts) = prev_timestamp {
+            if block.timestamp <= prev_ts {
+                return Err(format!("Timestamp did not increase at {} - previous was {} but block has {}", i, prev_ts, block.timestamp));
+            }
+        }
+
+        let expected_bits = expected_bits(&chain.blocks[..i], block.id);
+        if block.bits != expected_bits {
+            return Err(format!("Difficulty retarget mismatch at {} - expected bits {:08x} but block has {:08x}", i, expected_bits, block.bits));
+        }
+
+        for (tx_index, tx) in block.data.iter().enumerate() {
+            if !transaction::verify_transaction(tx) {
+                return Err(format!("Invalid transaction signature at block {} transaction {}", i, tx_index));
+            }
+        }
+
+        let expected_root = merkle::merkle_root(&block.data);
+        if block.merkle_root != expected_root {
+            return Err(format!("Merkle root mismatch at {} - expected {} but block stored {}", i, bytes_to_str(&expected_root), bytes_to_str(&block.merkle_root)));
+        }
 
         let hash = hash(block);
-        if !matches_difficulty(&hash, DIFFICULTY) {
-            return Err(format!("Hash target failure at {} - expected target {} but hash was {}", i, DIFFICULTY, bytes_to_str(&hash)));
+        let target = target_from_compact(block.bits);
+        if !matches_difficulty(&hash, &target) {
+            return Err(format!("Hash target failure at {} - expected target {:x} but hash was {}", i, target, bytes_to_str(&hash)));
         }
         prev_hash = hash;
+        prev_timestamp = Some(block.timestamp);
     }
 
     Ok(())
 }
 
+/// Determines the compact target a block at `height` must use, given the
+/// blocks preceding it. Bits carry over unchanged except right after a
+/// `RETARGET_INTERVAL`-block window closes, when they're recomputed from
+/// how long that window actually took.
+fn expected_bits(history: &[Block], height: u64) -> CompactTarget {
+    let prev = match history.last() {
+        Some(block) => block,
+        None => return INITIAL_BITS,
+    };
+
+    if height == 0 || !height.is_multiple_of(RETARGET_INTERVAL) {
+        return prev.bits;
+    }
+
+    let window_start = history.len() - (RETARGET_INTERVAL as usize);
+    let actual_seconds = prev.timestamp - history[window_start].timestamp;
+    retarget(prev.bits, actual_seconds)
+}
+
+/// Scales `prev_bits` by the ratio of `actual_seconds` to the desired
+/// window length, clamped to at most a `MAX_RETARGET_FACTOR`x move.
+fn retarget(prev_bits: CompactTarget, actual_seconds: i64) -> CompactTarget {
+    let desired_seconds = RETARGET_INTERVAL as i64 * TARGET_BLOCK_SECONDS;
+    let clamped_seconds = actual_seconds
+        .max(desired_seconds / MAX_RETARGET_FACTOR)
+        .min(desired_seconds * MAX_RETARGET_FACTOR);
+
+    let prev_target = target_from_compact(prev_bits);
+    let new_target = (prev_target * BigUint::from(clamped_seconds as u64)) / BigUint::from(desired_seconds as u64);
+    compact_from_target(&new_target)
+}
+
 fn bytes_to_str(arr: &[u8]) -> String {
     use std::fmt::Write;
 
@@ -99,50 +190,89 @@ fn bytes_to_str(arr: &[u8]) -> String {
     s
 }
 
-fn make_block(prev: Option<&Block>, data: Data) -> Block {
-    let prev_hash = prev.map(|b| hash(b)).unwrap_or(ZERO_HASH);
+fn make_block(history: &[Block], data: Data) -> Block {
+    let prev = history.last();
+    let prev_hash = prev.map(hash).unwrap_or(ZERO_HASH);
     let id = prev.map(|b| b.id + 1).unwrap_or(0);
+    let bits = expected_bits(history, id);
+    let merkle_root = merkle::merkle_root(&data);
+
+    // Timestamps must be strictly increasing; if the wall clock hasn't
+    // ticked forward since the previous block (e.g. mining in a tight
+    // loop), bump it by a second instead of stalling or violating the rule.
+    let now = Utc::now().timestamp();
+    let timestamp = match prev {
+        Some(p) if now <= p.timestamp => p.timestamp + 1,
+        _ => now,
+    };
+
     let mut block = Block {
-        id: id,
+        id,
         nonce: 0,
-        data: data,
-        prev_hash: prev_hash
+        salt: 0,
+        bits,
+        timestamp,
+        merkle_root,
+        data,
+        prev_hash,
     };
 
-    find_nonce(&mut block, DIFFICULTY);
+    find_nonce(&mut block, bits);
     block
 }
 
-fn find_nonce(block: &mut Block, difficulty: u32) {
-    for n in 0..<u32>::max_value() {
-        block.nonce = n;
-        let hash = hash(block);
-        if matches_difficulty(&hash, difficulty) {
-            return;
-        }
+/// Searches for a nonce meeting `bits` by splitting the 64-bit nonce space
+/// into disjoint strides across `num_cpus::get()` worker threads, each
+/// starting from a distinct offset. A random `salt` is assigned first so
+/// that two miners racing the same block never grind an identical search.
+/// The first worker to find a match signals the rest to stop via a shared
+/// `AtomicBool` and reports its nonce back over a channel.
+fn find_nonce(block: &mut Block, bits: CompactTarget) {
+    let target = target_from_compact(bits);
+    block.bits = bits;
+    block.salt = rand::random();
+
+    let worker_count = num_cpus::get().max(1) as u64;
+    let found = Arc::new(AtomicBool::new(false));
+    let (tx, rx) = mpsc::channel();
+    let template = block.clone();
+
+    let handles: Vec<_> = (0..worker_count).map(|worker| {
+        let target = target.clone();
+        let found = Arc::clone(&found);
+        let tx = tx.clone();
+        let mut candidate = template.clone();
+
+        thread::spawn(move || {
+            let mut n = worker;
+            while !found.load(Ordering::Relaxed) {
+                candidate.nonce = n;
+                let hash = hash(&candidate);
+                if matches_difficulty(&hash, &target) {
+                    found.store(true, Ordering::Relaxed);
+                    let _ = tx.send(n);
+                    return;
+                }
+                n = n.wrapping_add(worker_count);
+            }
+        })
+    }).collect();
+    drop(tx);
+
+    block.nonce = rx.recv().expect("all worker threads exited without finding a nonce");
+    for handle in handles {
+        let _ = handle.join();
     }
-
-    unreachable!("Unable to find nonce despite exhaustive search")
-}
-
-fn matches_difficulty(hash: &Hash, difficulty: u32) -> bool {
-    assert!(hash.len() * 8 >= (difficulty as usize));
-
-    leading_zero_bits(hash) >= difficulty
 }
 
-fn leading_zero_bits(hash: &Hash) -> u32 {
-    let mut zero_bits = 0;
-    for &byte in hash {
-        if byte == 0 {
-            zero_bits += 8;
-        } else {
-            zero_bits += byte.leading_zeros();
-            break;
-        }
+fn matches_difficulty(hash: &Hash, target: &BigUint) -> bool {
+    if *target == BigUint::from(0u32) {
+        // A zero target can never be met by any hash; reject explicitly
+        // rather than letting every hash trivially satisfy `<= 0`.
+        return false;
     }
 
-    zero_bits
+    BigUint::from_bytes_be(hash) <= *target
 }
 
 fn hash(block: &Block) -> Hash {
@@ -158,15 +288,27 @@ fn hash(block: &Block) -> Hash {
 fn as_bytes(block: &Block) -> Vec<u8> {
     let mut bytes = Vec::new();
     bytes.write_u64::<BigEndian>(block.id).unwrap();
-    bytes.write_u32::<BigEndian>(block.nonce).unwrap();
-    bytes.extend(&block.data);
+    bytes.write_u64::<BigEndian>(block.nonce).unwrap();
+    bytes.write_u64::<BigEndian>(block.salt).unwrap();
+    bytes.write_u32::<BigEndian>(block.bits).unwrap();
+    bytes.write_i64::<BigEndian>(block.timestamp).unwrap();
+    bytes.extend_from_slice(&block.merkle_root);
     bytes.extend_from_slice(&block.prev_hash);
     bytes
 }
 
 #[cfg(test)]
 mod test {
-    use super::{Blockchain, Chain, ZERO_HASH, find_nonce, hash};
+    use super::{retarget, Blockchain, Chain, ZERO_HASH, hash, RETARGET_INTERVAL, TARGET_BLOCK_SECONDS};
+    use super::keys::generate_keypair;
+    use super::target::target_from_compact;
+    use super::transaction::{sign_transaction, Transaction};
+
+    fn make_tx(amount: u64) -> Transaction {
+        let sender = generate_keypair();
+        let recipient = generate_keypair();
+        sign_transaction(&sender, recipient.public_key, amount)
+    }
 
     #[test]
     fn init_works() {
@@ -178,16 +320,16 @@ mod test {
     fn push_works() {
         let mut chain: Chain = Blockchain::init();
 
-        chain.push(vec![0,0,0,0]);
+        chain.push(vec![make_tx(4)]);
         assert_eq!(1, chain.blocks.len());
-        chain.push(vec![0,0,0,1]);
+        chain.push(vec![make_tx(1)]);
         assert_eq!(2, chain.blocks.len());
 
         let block1 = &chain.blocks[0];
         let block2 = &chain.blocks[1];
-        assert_eq!(vec![0,0,0,0], block1.data);
+        assert_eq!(4, block1.data[0].amount);
         assert_eq!(ZERO_HASH, block1.prev_hash);
-        assert_eq!(vec![0,0,0,1], block2.data);
+        assert_eq!(1, block2.data[0].amount);
         assert_eq!(hash(block1), block2.prev_hash);
     }
 
@@ -195,20 +337,54 @@ mod test {
     fn verify_works() {
         let mut chain: Chain = Blockchain::init();
 
-        chain.push(vec![1,2,3,4]);
-        chain.push(vec![0]);
-        chain.push(vec![5,6,7,8]);
+        chain.push(vec![make_tx(4)]);
+        chain.push(vec![make_tx(0)]);
+        chain.push(vec![make_tx(8)]);
 
         assert!(chain.verify().is_ok());
-        chain.blocks[1].data = vec![5];
+
+        let original_tx = chain.blocks[1].data[0].clone();
+        let mut tampered_tx = original_tx.clone();
+        tampered_tx.amount = 9999;
+        chain.blocks[1].data = vec![tampered_tx];
         assert!(chain.verify().is_err());
-        chain.blocks[1].data = vec![0];
+        chain.blocks[1].data = vec![original_tx];
         assert!(chain.verify().is_ok());
+
         chain.blocks[2].prev_hash = ZERO_HASH;
         assert!(chain.verify().is_err());
         chain.blocks[2].prev_hash = hash(&chain.blocks[1]);
         assert!(chain.verify().is_ok());
-        find_nonce(&mut chain.blocks[2], 1);
+
+        // Corrupting the stored bits (without re-mining) no longer matches
+        // the bits expected at this height, so verification reliably fails
+        // instead of depending on the odds of the mined hash missing the
+        // new target (a target of 1 that practically no hash can meet).
+        chain.blocks[2].bits = 0x03000001;
         assert!(chain.verify().is_err());
     }
+
+    #[test]
+    fn retarget_tightens_when_blocks_come_too_fast() {
+        let desired = RETARGET_INTERVAL as i64 * TARGET_BLOCK_SECONDS;
+        // Blocks arrived faster than desired, so difficulty should rise,
+        // meaning the next target must shrink.
+        let new_bits = retarget(0x200fffff, desired / 4);
+        assert!(target_from_compact(new_bits) < target_from_compact(0x200fffff));
+    }
+
+    #[test]
+    fn retarget_loosens_when_blocks_come_too_slow() {
+        let desired = RETARGET_INTERVAL as i64 * TARGET_BLOCK_SECONDS;
+        let new_bits = retarget(0x200fffff, desired * 4);
+        assert!(target_from_compact(new_bits) > target_from_compact(0x200fffff));
+    }
+
+    #[test]
+    fn retarget_clamps_extreme_swings() {
+        let desired = RETARGET_INTERVAL as i64 * TARGET_BLOCK_SECONDS;
+        let unclamped = retarget(0x200fffff, desired * 100);
+        let clamped = retarget(0x200fffff, desired * 4);
+        assert_eq!(target_from_compact(unclamped), target_from_compact(clamped));
+    }
 }
\ No newline at end of file
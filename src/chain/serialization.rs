@@ -1,8 +1,18 @@
 extern crate serde;
 extern crate serde_json;
+extern crate bincode;
+extern crate byteorder;
 
+use self::byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use chain::*;
 
+const MAGIC: [u8; 4] = *b"BCHN";
+// Bumped whenever the binary layout changes in a way old readers can't
+// tolerate, so a stale client can fail fast instead of misparsing.
+const FORMAT_VERSION: u32 = 1;
+const CHAIN_ID: u32 = 1;
+const HEADER_LEN: usize = 4 + 4 + 4;
+
 pub fn serialize(chain: &Chain) -> Vec<u8> {
     serde_json::to_vec(chain).unwrap()
 }
@@ -11,16 +21,85 @@ pub fn deserialize(data: &Vec<u8>) -> Result<Chain, String> {
     serde_json::from_slice(data.as_slice()).map_err(|_| "Could not deserialize chain".to_owned())
 }
 
+/// Encodes `chain` with `bincode`, prefixed by a small self-describing
+/// header (magic bytes, format version, chain id) so that future field
+/// additions can still be told apart from today's layout on the wire.
+pub fn serialize_bin(chain: &Chain) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&MAGIC);
+    bytes.write_u32::<BigEndian>(FORMAT_VERSION).unwrap();
+    bytes.write_u32::<BigEndian>(CHAIN_ID).unwrap();
+    bytes.extend(bincode::serialize(chain).unwrap());
+    bytes
+}
+
+pub fn deserialize_bin(data: &[u8]) -> Result<Chain, String> {
+    if data.len() < HEADER_LEN {
+        return Err("Chain data too short for header".to_owned());
+    }
+
+    let (header, body) = data.split_at(HEADER_LEN);
+    if header[0..4] != MAGIC {
+        return Err("Unrecognized magic bytes".to_owned());
+    }
+
+    let version = (&header[4..8]).read_u32::<BigEndian>().unwrap();
+    if version != FORMAT_VERSION {
+        return Err(format!("Unsupported chain format version {}", version));
+    }
+
+    let chain_id = (&header[8..12]).read_u32::<BigEndian>().unwrap();
+    if chain_id != CHAIN_ID {
+        return Err(format!("Unexpected chain id {}", chain_id));
+    }
+
+    bincode::deserialize(body).map_err(|_| "Could not deserialize chain".to_owned())
+}
+
 #[cfg(test)]
 mod test {
     use chain::{Chain, Blockchain};
-    use super::{serialize, deserialize};
+    use chain::keys::generate_keypair;
+    use chain::transaction::sign_transaction;
+    use super::{serialize, deserialize, serialize_bin, deserialize_bin};
+
+    fn sample_chain() -> Chain {
+        let alice = generate_keypair();
+        let bob = generate_keypair();
+
+        let mut chain: Chain = Blockchain::init();
+        chain.push(vec![sign_transaction(&alice, bob.public_key, 10)]);
+        chain.push(vec![sign_transaction(&bob, alice.public_key, 4)]);
+        chain
+    }
 
     #[test]
     fn serialize_deserialize_works() {
-        let chain: Chain = Blockchain::init();
+        let chain = sample_chain();
         let serialized = serialize(&chain);
         let deserialized = deserialize(&serialized);
         assert_eq!(chain, deserialized.unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn serialize_deserialize_bin_works() {
+        let chain = sample_chain();
+        let serialized = serialize_bin(&chain);
+        let deserialized = deserialize_bin(&serialized);
+        assert_eq!(chain, deserialized.unwrap());
+    }
+
+    #[test]
+    fn deserialize_bin_rejects_bad_magic() {
+        let mut serialized = serialize_bin(&sample_chain());
+        serialized[0] = serialized[0].wrapping_add(1);
+        assert!(deserialize_bin(&serialized).is_err());
+    }
+
+    #[test]
+    fn deserialize_bin_rejects_unsupported_version() {
+        let mut serialized = serialize_bin(&sample_chain());
+        serialized[7] = serialized[7].wrapping_add(1);
+        assert!(deserialize_bin(&serialized).is_err());
+    }
+}
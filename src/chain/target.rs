@@ -0,0 +1,92 @@
+extern crate num_bigint;
+
+use self::num_bigint::BigUint;
+
+/// Compact ("bits") encoding of a 256-bit target, mirroring the 4-byte
+/// representation used by Bitcoin: the top byte is an exponent and the
+/// remaining three bytes are a mantissa, so that
+/// `value = mantissa << (8 * (exponent - 3))`.
+pub type CompactTarget = u32;
+
+/// Decodes a compact target into the full `BigUint` it represents.
+///
+/// When `exponent <= 3` the mantissa would need to be shifted right rather
+/// than left (it only ever has significant bits in its lowest bytes), so
+/// that case is handled as a right shift instead of underflowing the
+/// `exponent - 3` subtraction.
+pub fn target_from_compact(bits: CompactTarget) -> BigUint {
+    let exponent = (bits >> 24) as i32;
+    let mantissa = BigUint::from(bits & 0x00ff_ffff);
+
+    if exponent <= 3 {
+        mantissa >> (8 * (3 - exponent)) as usize
+    } else {
+        mantissa << (8 * (exponent - 3)) as usize
+    }
+}
+
+/// Encodes a `BigUint` target back into its compact representation, the
+/// inverse of `target_from_compact`. Mirrors Bitcoin's `GetCompact`: the
+/// three most significant bytes become the mantissa and the number of
+/// bytes needed to hold the value becomes the exponent, nudging the
+/// mantissa down a byte whenever its top bit would otherwise collide with
+/// the exponent byte.
+pub fn compact_from_target(target: &BigUint) -> CompactTarget {
+    let bytes = target.to_bytes_be();
+    if bytes.iter().all(|&b| b == 0) {
+        return 0;
+    }
+
+    let mut size = bytes.len() as u32;
+    let mut mantissa = if bytes.len() <= 3 {
+        let mut padded = [0u8; 3];
+        padded[..bytes.len()].copy_from_slice(&bytes);
+        ((padded[0] as u32) << 16) | ((padded[1] as u32) << 8) | (padded[2] as u32)
+    } else {
+        ((bytes[0] as u32) << 16) | ((bytes[1] as u32) << 8) | (bytes[2] as u32)
+    };
+
+    if mantissa & 0x0080_0000 != 0 {
+        mantissa >>= 8;
+        size += 1;
+    }
+
+    (size << 24) | mantissa
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compact_from_target, target_from_compact};
+    use super::num_bigint::BigUint;
+
+    #[test]
+    fn decodes_positive_exponent() {
+        // mantissa 0x01, exponent 4 -> 0x01 << 8
+        assert_eq!(BigUint::from(0x01_00u32), target_from_compact(0x04000001));
+    }
+
+    #[test]
+    fn decodes_small_exponent_as_right_shift() {
+        // exponent <= 3 shifts the mantissa down instead of up:
+        // mantissa 0x000100, exponent 2 -> 0x000100 >> 8 == 0x01
+        assert_eq!(BigUint::from(0x00_01u32), target_from_compact(0x02000100));
+    }
+
+    #[test]
+    fn decodes_zero_target() {
+        assert_eq!(BigUint::from(0u32), target_from_compact(0x00000000));
+    }
+
+    #[test]
+    fn compact_from_target_round_trips() {
+        for &bits in &[0x1e0fffffu32, 0x200fffff, 0x03010000] {
+            let target = target_from_compact(bits);
+            assert_eq!(target, target_from_compact(compact_from_target(&target)));
+        }
+    }
+
+    #[test]
+    fn compact_from_target_of_zero_is_zero() {
+        assert_eq!(0, compact_from_target(&BigUint::from(0u32)));
+    }
+}
@@ -0,0 +1,142 @@
+extern crate byteorder;
+extern crate crypto;
+extern crate secp256k1;
+
+use self::byteorder::{BigEndian, WriteBytesExt};
+use self::crypto::digest::Digest;
+use self::crypto::sha2::Sha256;
+use self::secp256k1::{Secp256k1, Signature};
+use self::secp256k1::key::PublicKey;
+
+use chain::keys::{self, KeyPair};
+use chain::Hash;
+
+/// A single signed transfer from `sender` to `recipient`. Mirrors the
+/// `Option<Transaction>` shape used by external chains such as Alfis and
+/// rust-bitcoin: a block is simply a vector of these.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transaction {
+    #[serde(with = "serde_public_key")]
+    pub sender: PublicKey,
+    #[serde(with = "serde_public_key")]
+    pub recipient: PublicKey,
+    pub amount: u64,
+    #[serde(with = "serde_signature")]
+    pub signature: Signature,
+}
+
+/// `secp256k1`'s own `Serialize`/`Deserialize` impls deserialize through a
+/// borrowed `&[u8]`, which only self-describing formats that natively
+/// distinguish byte strings (such as `bincode`) can satisfy; `serde_json`
+/// has no byte-string wire type and fails to round-trip through them. These
+/// shims go through an owned `Vec<u8>` instead, which every format we use
+/// (`serde_json` and `bincode`) can deserialize.
+mod serde_public_key {
+    extern crate serde;
+
+    use self::serde::{Deserialize, Deserializer, Serializer};
+    use self::serde::de::Error;
+    use super::{PublicKey, Secp256k1};
+
+    pub fn serialize<S: Serializer>(key: &PublicKey, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_bytes(&key.serialize())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<PublicKey, D::Error> {
+        let bytes: Vec<u8> = Deserialize::deserialize(d)?;
+        let secp = Secp256k1::without_caps();
+        PublicKey::from_slice(&secp, &bytes).map_err(D::Error::custom)
+    }
+}
+
+mod serde_signature {
+    extern crate serde;
+
+    use self::serde::{Deserialize, Deserializer, Serializer};
+    use self::serde::de::Error;
+    use super::{Secp256k1, Signature};
+
+    pub fn serialize<S: Serializer>(signature: &Signature, s: S) -> Result<S::Ok, S::Error> {
+        let secp = Secp256k1::without_caps();
+        s.serialize_bytes(&signature.serialize_compact(&secp))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Signature, D::Error> {
+        let bytes: Vec<u8> = Deserialize::deserialize(d)?;
+        let secp = Secp256k1::without_caps();
+        Signature::from_compact(&secp, &bytes).map_err(D::Error::custom)
+    }
+}
+
+/// The bytes a transaction's signature commits to: everything except the
+/// signature itself.
+fn signing_bytes(sender: &PublicKey, recipient: &PublicKey, amount: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&sender.serialize());
+    bytes.extend_from_slice(&recipient.serialize());
+    bytes.write_u64::<BigEndian>(amount).unwrap();
+    bytes
+}
+
+fn sha256(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.input(data);
+
+    let mut result = [0; 32];
+    hasher.result(&mut result);
+    result
+}
+
+/// Builds and signs a transfer of `amount` from `sender` to `recipient`.
+pub fn sign_transaction(sender: &KeyPair, recipient: PublicKey, amount: u64) -> Transaction {
+    let digest = sha256(&signing_bytes(&sender.public_key, &recipient, amount));
+    let signature = keys::sign(&digest, &sender.private_key);
+
+    Transaction {
+        sender: sender.public_key,
+        recipient,
+        amount,
+        signature,
+    }
+}
+
+/// Checks that `tx.signature` is a valid signature by `tx.sender` over the
+/// transaction's canonical bytes.
+pub fn verify_transaction(tx: &Transaction) -> bool {
+    let digest = sha256(&signing_bytes(&tx.sender, &tx.recipient, tx.amount));
+    keys::verify(&digest, &tx.signature, &tx.sender)
+}
+
+/// Serializes a transaction, including its signature, for folding into a
+/// block's hash so that signatures commit to inclusion.
+pub fn as_bytes(tx: &Transaction) -> Vec<u8> {
+    let secp = Secp256k1::new();
+    let mut bytes = signing_bytes(&tx.sender, &tx.recipient, tx.amount);
+    bytes.extend_from_slice(&tx.signature.serialize_compact(&secp));
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sign_transaction, verify_transaction};
+    use chain::keys::generate_keypair;
+
+    #[test]
+    fn sign_and_verify_roundtrip() {
+        let sender = generate_keypair();
+        let recipient = generate_keypair();
+
+        let tx = sign_transaction(&sender, recipient.public_key, 42);
+        assert!(verify_transaction(&tx));
+    }
+
+    #[test]
+    fn tampered_amount_fails_verification() {
+        let sender = generate_keypair();
+        let recipient = generate_keypair();
+
+        let mut tx = sign_transaction(&sender, recipient.public_key, 42);
+        tx.amount = 1337;
+        assert!(!verify_transaction(&tx));
+    }
+}
@@ -7,25 +7,41 @@ extern crate env_logger;
 mod chain;
 
 use chain::Blockchain;
-use chain::serialization::{serialize, deserialize};
+use chain::keys::generate_keypair;
+use chain::merkle::{merkle_proof, verify_merkle_proof};
+use chain::serialization::{deserialize, deserialize_bin, serialize, serialize_bin};
+use chain::transaction::sign_transaction;
 
 fn main() {
     env_logger::init().unwrap();
 
+    let alice = generate_keypair();
+    let bob = generate_keypair();
+
     let mut ch: chain::Chain = Blockchain::init();
-    ch.push(vec![0,0,0,0]);
+    ch.push(vec![sign_transaction(&alice, bob.public_key, 10)]);
     println!("{:?}", ch);
-    ch.push(vec![0,0,0,1]);
+    ch.push(vec![sign_transaction(&bob, alice.public_key, 4)]);
     println!("{:?}", ch);
-    ch.push(vec![0,0,0,2]);
+    ch.push(vec![sign_transaction(&alice, bob.public_key, 1)]);
     println!("{:?}", ch);
     verify_chain(&ch);
 
-    let serialized = String::from_utf8(serialize(&ch)).unwrap();
-    println!("{}", serialized);
+    let first_block = &ch.blocks[0];
+    let proof = merkle_proof(&first_block.data, 0);
+    let leaf = chain::merkle::leaf_hash(&first_block.data[0]);
+    println!("First transaction's Merkle proof verifies: {}", verify_merkle_proof(leaf, &proof, first_block.merkle_root));
+
+    let serialized = serialize(&ch);
+    let deserialized = deserialize(&serialized).unwrap();
+    println!("Round-tripped chain through JSON ({} bytes): {}", serialized.len(), ch == deserialized);
+
+    let serialized_bin = serialize_bin(&ch);
+    let deserialized_bin = deserialize_bin(&serialized_bin).unwrap();
+    println!("Round-tripped chain through bincode ({} bytes): {}", serialized_bin.len(), ch == deserialized_bin);
 
     println!("Changing block 1");
-    ch.blocks[1].data = vec![0,0,0,0];
+    ch.blocks[1].data = vec![sign_transaction(&alice, bob.public_key, 1000)];
     verify_chain(&ch);
 }
 